@@ -1,13 +1,459 @@
+use base64::Engine;
+use chromiumoxide::browser::{Browser, BrowserConfig};
+use chromiumoxide::page::Page;
 use chrono::Utc;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use futures::stream::{self, StreamExt};
 use log::LevelFilter;
 use reqwest::Url;
 use scraper::{ElementRef, Html, Selector};
-use std::path::Path;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 const BASE_URL: &str = "https://findmyfrens.net/";
 const SNAPSHOT_BASE_DIR: &str = "snapshot";
 const TIMESTAMP_FMT: &str = "%Y%m%d%H%M%S";
+const OBJECTS_DIR_NAME: &str = "objects";
+const MANIFEST_FILE_NAME: &str = "manifest.tsv";
+const LINKS_FILE_NAME: &str = "links.tsv";
+
+#[derive(Serialize)]
+struct Link {
+    title: String,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct User {
+    screen_name: String,
+    display_name: String,
+    links: Vec<Link>,
+}
+
+#[derive(Clone, ValueEnum)]
+#[clap(rename_all = "lowercase")]
+enum OutputFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+/// Serializes `User`s to one of the supported output formats as they
+/// arrive. `Json` writes each user as an element of a single array, opened
+/// with `[` before the first user and closed with `]` by `finish`; `Ndjson`
+/// writes one JSON object per line; `Csv` writes one row per link. Generic over the
+/// sink `W` so the framing logic can be exercised against an in-memory
+/// buffer in tests, rather than only against stdout.
+enum Writer<W: Write> {
+    Csv(csv::Writer<W>),
+    Json { out: W, first: bool },
+    Ndjson(W),
+}
+
+impl Writer<std::io::Stdout> {
+    fn new(format: OutputFormat) -> Result<Self, Error> {
+        match format {
+            OutputFormat::Csv => Ok(Writer::Csv(
+                csv::WriterBuilder::new().from_writer(std::io::stdout()),
+            )),
+            OutputFormat::Json => Ok(Writer::Json {
+                out: std::io::stdout(),
+                first: true,
+            }),
+            OutputFormat::Ndjson => Ok(Writer::Ndjson(std::io::stdout())),
+        }
+    }
+}
+
+impl<W: Write> Writer<W> {
+    fn write_user(&mut self, user: &User) -> Result<(), Error> {
+        match self {
+            Writer::Csv(writer) => {
+                for link in &user.links {
+                    writer.write_record(&[
+                        &user.screen_name,
+                        &user.display_name,
+                        &link.title,
+                        &link.url,
+                    ])?;
+                }
+            }
+            Writer::Json { out, first } => {
+                write!(out, "{}", if *first { "[" } else { "," })?;
+                *first = false;
+                serde_json::to_writer(&mut *out, user)?;
+            }
+            Writer::Ndjson(out) => {
+                serde_json::to_writer(&mut *out, user)?;
+                writeln!(out)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Writer::Json { out, first } = self {
+            if *first {
+                write!(out, "[")?;
+            }
+            writeln!(out, "]")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counters accumulated across the whole run for the end-of-run summary
+/// table, shared across the concurrently-running user fetches.
+#[derive(Default)]
+struct Stats {
+    users_processed: AtomicU64,
+    links_extracted: AtomicU64,
+    /// In `--embed` mode this double-counts: each inlined asset's raw bytes
+    /// are added as it's fetched, and the assembled `index.html` (which
+    /// already contains those bytes, base64-inflated) is added again once
+    /// it's written. Treat this metric as "bytes moved", not "bytes on
+    /// disk", when embedding is on.
+    snapshot_bytes: AtomicU64,
+    warnings: AtomicU64,
+    failures: AtomicU64,
+    /// Assets `embed_assets` couldn't inline because its anchor tag could
+    /// no longer be located in the document (see `replace_attr_value`). A
+    /// nonzero count means a `--embed` snapshot isn't actually
+    /// self-contained, despite the file extension suggesting otherwise.
+    assets_not_inlined: AtomicU64,
+}
+
+/// The shared HTTP client and retry policy used for every plain-HTTP
+/// request the scraper makes.
+struct HttpConfig {
+    client: reqwest::Client,
+    max_retries: u32,
+}
+
+/// An asset we've previously downloaded: its content hash, plus whatever
+/// validators the origin server gave us for it, so a later run can ask
+/// "is this still current?" instead of trusting the URL forever.
+#[derive(Clone, Default)]
+struct CachedAsset {
+    hash: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// A content-addressed store of downloaded assets shared across every
+/// per-timestamp snapshot, keyed by the sha256 of their bytes. Asset URLs
+/// that were already fetched in a prior run (recorded in `manifest.tsv`)
+/// are revalidated with a conditional GET rather than assumed unchanged,
+/// since a URL is not a guarantee that its content is immutable.
+struct ObjectStore {
+    base_dir: PathBuf,
+    known: Mutex<HashMap<String, CachedAsset>>,
+}
+
+impl ObjectStore {
+    fn load(base_dir: PathBuf) -> Self {
+        let known = std::fs::read_to_string(base_dir.join(MANIFEST_FILE_NAME))
+            .ok()
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| {
+                        let mut fields = line.splitn(4, '\t');
+                        let url = fields.next()?;
+                        let hash = fields.next()?;
+                        let etag = fields.next().filter(|s| !s.is_empty()).map(str::to_string);
+                        let last_modified =
+                            fields.next().filter(|s| !s.is_empty()).map(str::to_string);
+                        Some((
+                            url.to_string(),
+                            CachedAsset {
+                                hash: hash.to_string(),
+                                etag,
+                                last_modified,
+                            },
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            base_dir,
+            known: Mutex::new(known),
+        }
+    }
+
+    fn objects_dir(&self) -> PathBuf {
+        self.base_dir.join(OBJECTS_DIR_NAME)
+    }
+
+    fn save_manifest(&self) -> Result<(), Error> {
+        let known = self.known.lock().unwrap();
+        let mut lines: Vec<String> = known
+            .iter()
+            .map(|(url, asset)| {
+                format!(
+                    "{url}\t{}\t{}\t{}",
+                    asset.hash,
+                    asset.etag.as_deref().unwrap_or(""),
+                    asset.last_modified.as_deref().unwrap_or("")
+                )
+            })
+            .collect();
+        lines.sort();
+
+        std::fs::create_dir_all(&self.base_dir)?;
+        std::fs::write(self.base_dir.join(MANIFEST_FILE_NAME), lines.join("\n"))?;
+
+        Ok(())
+    }
+
+    /// Fetches `url`'s bytes. If we've seen this URL before, revalidates it
+    /// with a conditional GET using the validators we recorded for it: a
+    /// `304` confirms the object on disk is still current and the network
+    /// body is skipped, while a fresh `200` is hashed like any other fetch
+    /// (which also covers servers that return no validators at all, where
+    /// every call is a full re-fetch compared by hash).
+    async fn fetch(&self, url: &Url, http: &HttpConfig) -> Result<(String, Vec<u8>), Error> {
+        let cached = self.known.lock().unwrap().get(url.as_str()).cloned();
+        let conditional = cached.as_ref().map(|asset| Conditional {
+            etag: asset.etag.clone(),
+            last_modified: asset.last_modified.clone(),
+        });
+
+        match fetch_bytes_conditional(url, http, conditional.as_ref()).await? {
+            ConditionalFetch::NotModified => match cached {
+                Some(cached) => {
+                    let bytes = std::fs::read(self.objects_dir().join(&cached.hash))?;
+                    Ok((cached.hash, bytes))
+                }
+                // A server (or an intermediary caching proxy) sent us a 304
+                // even though we had nothing cached to validate against, and
+                // therefore sent no `If-None-Match`/`If-Modified-Since`. We
+                // can't trust an object we don't have, so fall through to an
+                // unconditional GET rather than panicking the whole run.
+                None => {
+                    log::warn!(
+                        "{url} returned 304 Not Modified to an unconditional request; retrying with a full GET"
+                    );
+                    match fetch_bytes_conditional(url, http, None).await? {
+                        ConditionalFetch::Modified {
+                            bytes,
+                            etag,
+                            last_modified,
+                        } => self.store(url, bytes, etag, last_modified),
+                        ConditionalFetch::NotModified => Err(Error::InvalidHtml(format!(
+                            "{url} returned 304 Not Modified to an unconditional GET"
+                        ))),
+                    }
+                }
+            },
+            ConditionalFetch::Modified {
+                bytes,
+                etag,
+                last_modified,
+            } => self.store(url, bytes, etag, last_modified),
+        }
+    }
+
+    /// Hashes `bytes`, writes them to the object store if they're new, and
+    /// records `url`'s validators for future revalidation.
+    fn store(
+        &self,
+        url: &Url,
+        bytes: Vec<u8>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    ) -> Result<(String, Vec<u8>), Error> {
+        let hash = format!("{:x}", Sha256::digest(&bytes));
+
+        let objects_dir = self.objects_dir();
+        std::fs::create_dir_all(&objects_dir)?;
+        let object_path = objects_dir.join(&hash);
+        if !object_path.exists() {
+            std::fs::write(&object_path, &bytes)?;
+        }
+
+        self.known.lock().unwrap().insert(
+            url.as_str().to_string(),
+            CachedAsset {
+                hash: hash.clone(),
+                etag,
+                last_modified,
+            },
+        );
+
+        Ok((hash, bytes))
+    }
+
+    /// Hard-links `hash`'s object into a per-timestamp snapshot directory
+    /// under a logical filename.
+    fn link_into<P: AsRef<Path>>(&self, hash: &str, dest: P) -> Result<(), Error> {
+        if dest.as_ref().exists() {
+            std::fs::remove_file(dest.as_ref())?;
+        }
+        std::fs::hard_link(self.objects_dir().join(hash), dest)?;
+
+        Ok(())
+    }
+}
+
+/// How page HTML is fetched. The default `Http` path is a plain request
+/// (retried per `HttpConfig`); `Render` drives a headless Chromium
+/// instance instead, for sites whose link lists are populated client-side.
+enum Fetcher<'a> {
+    Http(&'a HttpConfig),
+    Render {
+        browser: &'a Browser,
+        wait_selector: Option<&'a str>,
+        wait_timeout: Duration,
+    },
+}
+
+async fn fetch_page_html(url: &Url, fetcher: &Fetcher<'_>) -> Result<String, Error> {
+    match fetcher {
+        Fetcher::Http(http) => {
+            let response = send_with_retry(http, url, None).await?;
+            Ok(response.text().await?)
+        }
+        Fetcher::Render {
+            browser,
+            wait_selector,
+            wait_timeout,
+        } => {
+            let page = browser.new_page(url.as_str()).await?;
+            let result = render_page(&page, wait_selector.as_deref(), *wait_timeout).await;
+            page.close().await?;
+            result
+        }
+    }
+}
+
+/// Waits for navigation to settle on an already-opened page, then returns its
+/// rendered HTML. Split out of `fetch_page_html` so the caller can close the
+/// page on both the success and error paths.
+async fn render_page(
+    page: &Page,
+    wait_selector: Option<&str>,
+    wait_timeout: Duration,
+) -> Result<String, Error> {
+    page.wait_for_navigation().await?;
+    if let Some(selector) = wait_selector {
+        tokio::time::timeout(wait_timeout, page.find_element(selector))
+            .await
+            .map_err(|_| Error::Render(format!("Timed out waiting for \"{selector}\"")))??;
+    } else {
+        tokio::time::sleep(wait_timeout).await;
+    }
+    Ok(page.content().await?)
+}
+
+/// Validators captured from a prior response for a cached asset, sent back
+/// as `If-None-Match`/`If-Modified-Since` so the origin server can confirm
+/// the bytes we have on disk are still current with a `304` instead of us
+/// re-downloading them.
+#[derive(Clone, Default)]
+struct Conditional {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Sends a GET request, retrying connection errors and retryable status
+/// codes with exponential backoff (honoring `Retry-After` when present)
+/// until `http.max_retries` is exhausted. `conditional`'s validators, if
+/// any, are attached to the request; a resulting `304 Not Modified` is
+/// treated the same as a 2xx, since it's a valid terminal response rather
+/// than an error.
+async fn send_with_retry(
+    http: &HttpConfig,
+    url: &Url,
+    conditional: Option<&Conditional>,
+) -> Result<reqwest::Response, Error> {
+    let mut backoff = Duration::from_millis(500);
+
+    for attempt in 0..=http.max_retries {
+        let mut request = http.client.get(url.clone());
+        if let Some(conditional) = conditional {
+            if let Some(etag) = &conditional.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &conditional.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        match request.send().await {
+            Ok(response)
+                if response.status().is_success()
+                    || response.status() == reqwest::StatusCode::NOT_MODIFIED =>
+            {
+                return Ok(response)
+            }
+            Ok(response) => {
+                if !is_retryable_status(response.status()) {
+                    return Err(response.error_for_status().unwrap_err().into());
+                }
+                if attempt == http.max_retries {
+                    return Err(Error::RetriesExhausted(url.to_string()));
+                }
+                let wait = retry_after(&response).unwrap_or(backoff);
+                log::warn!(
+                    "Retrying {} after {:?} (status {}, attempt {}/{})",
+                    url,
+                    wait,
+                    response.status(),
+                    attempt + 1,
+                    http.max_retries
+                );
+                tokio::time::sleep(wait).await;
+            }
+            Err(error) => {
+                if !(error.is_connect() || error.is_timeout()) {
+                    return Err(Error::HttpClient(error));
+                }
+                if attempt == http.max_retries {
+                    return Err(Error::RetriesExhausted(url.to_string()));
+                }
+                log::warn!(
+                    "Retrying {} after {:?} ({}, attempt {}/{})",
+                    url,
+                    backoff,
+                    error,
+                    attempt + 1,
+                    http.max_retries
+                );
+                tokio::time::sleep(backoff).await;
+            }
+        }
+
+        backoff *= 2;
+    }
+
+    unreachable!("the loop above always returns on its final iteration")
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -21,7 +467,48 @@ async fn main() -> Result<(), Error> {
         Some(Path::new(SNAPSHOT_BASE_DIR).join(Utc::now().format(TIMESTAMP_FMT).to_string()))
     };
 
-    let index = get_html(&base_url, snapshot_dir.as_ref()).await?;
+    let stats = Arc::new(Stats::default());
+
+    let mut client_builder = reqwest::Client::builder().timeout(Duration::from_secs(opts.timeout));
+    if let Some(user_agent) = &opts.user_agent {
+        client_builder = client_builder.user_agent(user_agent);
+    }
+    if let Some(proxy) = &opts.proxy {
+        client_builder = client_builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    let http = HttpConfig {
+        client: client_builder.build()?,
+        max_retries: opts.max_retries,
+    };
+    let objects = ObjectStore::load(Path::new(SNAPSHOT_BASE_DIR).to_path_buf());
+
+    let browser = if opts.render {
+        let (browser, mut handler) =
+            Browser::launch(BrowserConfig::builder().build().map_err(Error::Render)?).await?;
+        tokio::spawn(async move { while handler.next().await.is_some() {} });
+        Some(browser)
+    } else {
+        None
+    };
+    let fetcher = match &browser {
+        Some(browser) => Fetcher::Render {
+            browser,
+            wait_selector: opts.render_wait_selector.as_deref(),
+            wait_timeout: Duration::from_millis(opts.render_timeout_ms),
+        },
+        None => Fetcher::Http(&http),
+    };
+
+    let index = get_html(
+        &base_url,
+        snapshot_dir.as_ref(),
+        opts.embed,
+        &stats,
+        &fetcher,
+        &http,
+        &objects,
+    )
+    .await?;
     let users = index
         .select(&BODY_LIST_SEL)
         .map(parse_a)
@@ -29,43 +516,234 @@ async fn main() -> Result<(), Error> {
 
     log::info!("Downloading {} users", users.len());
 
-    let mut writer = csv::WriterBuilder::new().from_writer(std::io::stdout());
+    let user_tasks = users
+        .into_iter()
+        .map(|(raw_url, display_name)| {
+            let user_url = base_url.join(&raw_url)?;
+            let screen_name = raw_url
+                .trim_end_matches('/')
+                .split('/')
+                .last()
+                .ok_or_else(|| Error::InvalidHtml("Missing screen name".to_string()))?
+                .to_string();
+            let user_snapshot_dir: Option<PathBuf> =
+                snapshot_dir.as_ref().map(|dir| dir.join(&screen_name));
 
-    for (raw_url, display_name) in users {
-        let user_url = base_url.join(&raw_url)?;
-        let screen_name = raw_url
-            .trim_end_matches('/')
-            .split('/')
-            .last()
-            .ok_or_else(|| Error::InvalidHtml("Missing screen name".to_string()))?;
+            Ok((screen_name, display_name, user_url, user_snapshot_dir))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
 
-        for (url, title) in get_user(
-            &user_url,
-            snapshot_dir.as_ref().map(|dir| dir.join(screen_name)),
-            screen_name,
-            &display_name,
+    let progress = indicatif::ProgressBar::new(user_tasks.len() as u64);
+    progress.set_style(
+        indicatif::ProgressStyle::with_template(
+            "{spinner} [{elapsed_precise}] [{bar:40}] {pos}/{len} users (eta {eta})",
         )
-        .await?
-        {
-            writer.write_record(&[screen_name, &display_name, &title, &url])?;
+        .unwrap(),
+    );
+
+    let mut results = stream::iter(user_tasks)
+        .map(|(screen_name, display_name, user_url, user_snapshot_dir)| {
+            let embed = opts.embed;
+            let stats = Arc::clone(&stats);
+            let fetcher = &fetcher;
+            let http = &http;
+            let objects = &objects;
+            async move {
+                let rows = get_user(
+                    &user_url,
+                    user_snapshot_dir,
+                    &screen_name,
+                    &display_name,
+                    embed,
+                    &stats,
+                    fetcher,
+                    http,
+                    objects,
+                )
+                .await;
+                (screen_name, display_name, rows)
+            }
+        })
+        .buffer_unordered(opts.concurrency);
+
+    let mut writer = Writer::new(opts.format)?;
+    let mut all_rows: Vec<(String, String, String, String)> = Vec::new();
+
+    while let Some((screen_name, display_name, rows)) = results.next().await {
+        progress.inc(1);
+        stats.users_processed.fetch_add(1, Ordering::Relaxed);
+        match rows {
+            Ok(rows) => {
+                stats
+                    .links_extracted
+                    .fetch_add(rows.len() as u64, Ordering::Relaxed);
+                let links: Vec<Link> = rows
+                    .into_iter()
+                    .map(|(url, title)| {
+                        all_rows.push((
+                            screen_name.clone(),
+                            display_name.clone(),
+                            title.clone(),
+                            url.clone(),
+                        ));
+                        Link { title, url }
+                    })
+                    .collect();
+                writer.write_user(&User {
+                    screen_name,
+                    display_name,
+                    links,
+                })?;
+            }
+            Err(error) => {
+                stats.failures.fetch_add(1, Ordering::Relaxed);
+                log::error!(
+                    "Failed to download {} ({}): {}",
+                    screen_name,
+                    display_name,
+                    error
+                );
+            }
         }
     }
+    writer.finish()?;
+
+    progress.finish_and_clear();
+
+    if let Some(snapshot_dir) = &snapshot_dir {
+        objects.save_manifest()?;
+        write_links_file(&snapshot_dir.join(LINKS_FILE_NAME), &all_rows)?;
+    }
+    if let Some(since) = &opts.since {
+        let since_links = Path::new(SNAPSHOT_BASE_DIR)
+            .join(since)
+            .join(LINKS_FILE_NAME);
+        print_diff(&read_links_file(&since_links)?, &to_link_map(&all_rows));
+    }
+
+    print_summary(&stats);
 
     Ok(())
 }
 
+fn write_links_file(path: &Path, rows: &[(String, String, String, String)]) -> Result<(), Error> {
+    let mut lines: Vec<String> = rows
+        .iter()
+        .map(|(screen_name, _, _, url)| format!("{screen_name}\t{url}"))
+        .collect();
+    lines.sort();
+    std::fs::write(path, lines.join("\n"))?;
+
+    Ok(())
+}
+
+fn read_links_file(path: &Path) -> Result<HashMap<String, HashSet<String>>, Error> {
+    let mut links: HashMap<String, HashSet<String>> = HashMap::new();
+    let contents = std::fs::read_to_string(path)?;
+    for line in contents.lines() {
+        if let Some((screen_name, url)) = line.split_once('\t') {
+            links
+                .entry(screen_name.to_string())
+                .or_default()
+                .insert(url.to_string());
+        }
+    }
+
+    Ok(links)
+}
+
+fn to_link_map(rows: &[(String, String, String, String)]) -> HashMap<String, HashSet<String>> {
+    let mut links: HashMap<String, HashSet<String>> = HashMap::new();
+    for (screen_name, _, _, url) in rows {
+        links
+            .entry(screen_name.clone())
+            .or_default()
+            .insert(url.clone());
+    }
+
+    links
+}
+
+/// Prints a table of which users gained or lost links between a prior
+/// snapshot (`old`) and the current run (`new`).
+fn print_diff(old: &HashMap<String, HashSet<String>>, new: &HashMap<String, HashSet<String>>) {
+    let mut screen_names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    screen_names.sort();
+    screen_names.dedup();
+
+    let mut table = comfy_table::Table::new();
+    table.set_header(vec!["screen_name", "gained", "lost"]);
+
+    for screen_name in screen_names {
+        let empty = HashSet::new();
+        let old_links = old.get(screen_name).unwrap_or(&empty);
+        let new_links = new.get(screen_name).unwrap_or(&empty);
+        let gained = new_links.difference(old_links).count();
+        let lost = old_links.difference(new_links).count();
+
+        if gained > 0 || lost > 0 {
+            table.add_row(vec![
+                screen_name.clone(),
+                gained.to_string(),
+                lost.to_string(),
+            ]);
+        }
+    }
+
+    eprintln!("{table}");
+}
+
+fn print_summary(stats: &Stats) {
+    let mut table = comfy_table::Table::new();
+    table.set_header(vec!["metric", "value"]);
+    table.add_row(vec![
+        "users processed".to_string(),
+        stats.users_processed.load(Ordering::Relaxed).to_string(),
+    ]);
+    table.add_row(vec![
+        "links extracted".to_string(),
+        stats.links_extracted.load(Ordering::Relaxed).to_string(),
+    ]);
+    // Double-counted in `--embed` mode; see the doc comment on
+    // `Stats::snapshot_bytes`.
+    table.add_row(vec![
+        "snapshot bytes written".to_string(),
+        stats.snapshot_bytes.load(Ordering::Relaxed).to_string(),
+    ]);
+    table.add_row(vec![
+        "display name mismatches".to_string(),
+        stats.warnings.load(Ordering::Relaxed).to_string(),
+    ]);
+    table.add_row(vec![
+        "failed user fetches".to_string(),
+        stats.failures.load(Ordering::Relaxed).to_string(),
+    ]);
+    table.add_row(vec![
+        "assets not inlined".to_string(),
+        stats.assets_not_inlined.load(Ordering::Relaxed).to_string(),
+    ]);
+
+    eprintln!("{table}");
+}
+
 async fn get_user<P: AsRef<Path>>(
     url: &Url,
     snapshot_dir: Option<P>,
     screen_name: &str,
     display_name: &str,
+    embed: bool,
+    stats: &Stats,
+    fetcher: &Fetcher<'_>,
+    http: &HttpConfig,
+    objects: &ObjectStore,
 ) -> Result<Vec<(String, String)>, Error> {
     log::info!("Downloading {} ({})", screen_name, display_name);
-    let doc = get_html(url, snapshot_dir).await?;
+    let doc = get_html(url, snapshot_dir, embed, stats, fetcher, http, objects).await?;
 
     if let Some(h1) = doc.select(&BODY_MAIN_H1).collect::<Vec<_>>().first() {
         let h1_text = h1.inner_html();
         if h1_text.trim() != display_name {
+            stats.warnings.fetch_add(1, Ordering::Relaxed);
             log::warn!(
                 "Expected \"{}\", found \"{}\"",
                 display_name,
@@ -77,40 +755,280 @@ async fn get_user<P: AsRef<Path>>(
     doc.select(&BODY_MAIN_LIST_SEL).map(parse_a).collect()
 }
 
-async fn get_html<P: AsRef<Path>>(url: &Url, snapshot_dir: Option<P>) -> Result<Html, Error> {
-    let response = reqwest::get(url.clone()).await?;
-    let text = response.text().await?;
+async fn get_html<P: AsRef<Path>>(
+    url: &Url,
+    snapshot_dir: Option<P>,
+    embed: bool,
+    stats: &Stats,
+    fetcher: &Fetcher<'_>,
+    http: &HttpConfig,
+    objects: &ObjectStore,
+) -> Result<Html, Error> {
+    let text = fetch_page_html(url, fetcher).await?;
     let doc = Html::parse_document(&text);
 
     if let Some(snapshot_dir) = snapshot_dir {
         std::fs::create_dir_all(&snapshot_dir)?;
-        std::fs::write(snapshot_dir.as_ref().join("index.html"), text)?;
-        if let Some((stylesheet_url, stylesheet_filename)) = get_stylesheet(&doc, url)? {
-            save_file(
-                stylesheet_url,
-                snapshot_dir.as_ref().join(stylesheet_filename),
-            )
-            .await?;
-        }
-        if let Some((banner_url, banner_filename)) = get_img(&doc, url, &BANNER_IMG_SEL)? {
-            save_file(banner_url, snapshot_dir.as_ref().join(banner_filename)).await?;
-        }
-        if let Some((profile_url, profile_filename)) = get_img(&doc, url, &PROFILE_IMG_SEL)? {
-            save_file(profile_url, snapshot_dir.as_ref().join(profile_filename)).await?;
+
+        if embed {
+            let embedded = embed_assets(&text, &doc, url, stats, http, objects).await?;
+            stats
+                .snapshot_bytes
+                .fetch_add(embedded.len() as u64, Ordering::Relaxed);
+            std::fs::write(snapshot_dir.as_ref().join("index.html"), embedded)?;
+        } else {
+            stats
+                .snapshot_bytes
+                .fetch_add(text.len() as u64, Ordering::Relaxed);
+            std::fs::write(snapshot_dir.as_ref().join("index.html"), text)?;
+            if let Some((stylesheet_url, stylesheet_filename)) = get_stylesheet(&doc, url)? {
+                save_file(
+                    stylesheet_url,
+                    snapshot_dir.as_ref().join(stylesheet_filename),
+                    stats,
+                    http,
+                    objects,
+                )
+                .await?;
+            }
+            if let Some((banner_url, banner_filename)) = get_img(&doc, url, &BANNER_IMG_SEL)? {
+                save_file(
+                    banner_url,
+                    snapshot_dir.as_ref().join(banner_filename),
+                    stats,
+                    http,
+                    objects,
+                )
+                .await?;
+            }
+            if let Some((profile_url, profile_filename)) = get_img(&doc, url, &PROFILE_IMG_SEL)? {
+                save_file(
+                    profile_url,
+                    snapshot_dir.as_ref().join(profile_filename),
+                    stats,
+                    http,
+                    objects,
+                )
+                .await?;
+            }
         }
     }
 
     Ok(doc)
 }
 
-async fn save_file<P: AsRef<Path>>(url: Url, path: P) -> Result<(), Error> {
-    let response = reqwest::get(url).await?;
-    let bytes = response.bytes().await?;
-    std::fs::write(path, bytes)?;
+async fn save_file<P: AsRef<Path>>(
+    url: Url,
+    path: P,
+    stats: &Stats,
+    http: &HttpConfig,
+    objects: &ObjectStore,
+) -> Result<(), Error> {
+    let (hash, bytes) = objects.fetch(&url, http).await?;
+    stats
+        .snapshot_bytes
+        .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+    objects.link_into(&hash, path)?;
 
     Ok(())
 }
 
+/// The outcome of a [`fetch_bytes_conditional`] call: either the origin
+/// server confirmed the validators we sent are still current, or it sent
+/// back a (possibly new) body along with whatever validators it gave us
+/// for next time.
+enum ConditionalFetch {
+    NotModified,
+    Modified {
+        bytes: Vec<u8>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// Fetches `url`, sending `conditional`'s validators (if any) so an asset
+/// that hasn't changed can be confirmed with a `304` instead of its body
+/// being re-downloaded in full.
+async fn fetch_bytes_conditional(
+    url: &Url,
+    http: &HttpConfig,
+    conditional: Option<&Conditional>,
+) -> Result<ConditionalFetch, Error> {
+    let response = send_with_retry(http, url, conditional).await?;
+
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(ConditionalFetch::NotModified);
+    }
+
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let last_modified = response
+        .headers()
+        .get(reqwest::header::LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let bytes = response.bytes().await?.to_vec();
+
+    Ok(ConditionalFetch::Modified {
+        bytes,
+        etag,
+        last_modified,
+    })
+}
+
+/// Rewrites `text` so that every external asset it references (the
+/// stylesheet and every `<img>` element, plus anything pulled in by
+/// `url(...)` references inside the stylesheet) is replaced by an inline
+/// `data:` URL, producing a single self-contained HTML document.
+///
+/// Each substitution is anchored to the element's own serialized tag via
+/// [`replace_attr_value`], rather than a bare `out.replace(value, ...)`
+/// against the whole document, so a `href`/`src` value that happens to
+/// recur elsewhere (another element's attribute, a text node, a
+/// previously-inlined `data:` URL) is left alone. Within the element's own
+/// tag, the first occurrence of the value is the one replaced.
+async fn embed_assets(
+    text: &str,
+    doc: &Html,
+    base_url: &Url,
+    stats: &Stats,
+    http: &HttpConfig,
+    objects: &ObjectStore,
+) -> Result<String, Error> {
+    let mut out = text.to_string();
+
+    if let Some((stylesheet_url, _)) = get_stylesheet(doc, base_url)? {
+        if let Some(link) = doc.select(&STYLESHEET_SEL).next() {
+            let href = link.value().attr("href").ok_or_else(|| {
+                Error::InvalidHtml("Missing href for stylesheet link".to_string())
+            })?;
+            let (_, css) = objects.fetch(&stylesheet_url, http).await?;
+            stats
+                .snapshot_bytes
+                .fetch_add(css.len() as u64, Ordering::Relaxed);
+            let css_text = String::from_utf8_lossy(&css).into_owned();
+            let embedded_css =
+                embed_css_urls(&css_text, &stylesheet_url, stats, http, objects).await?;
+            let data_url = to_data_url("text/css", embedded_css.as_bytes());
+            if !replace_attr_value(&mut out, &link.html(), href, &data_url) {
+                stats.assets_not_inlined.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    for img in doc.select(&IMG_SEL) {
+        if let Some(src) = img.value().attr("src") {
+            let img_url = base_url.join(src)?;
+            let (_, bytes) = objects.fetch(&img_url, http).await?;
+            stats
+                .snapshot_bytes
+                .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            let data_url = to_data_url(guess_mime(src), &bytes);
+            if !replace_attr_value(&mut out, &img.html(), src, &data_url) {
+                stats.assets_not_inlined.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Replaces `value` with `data_url` inside the first occurrence of `tag`
+/// (an element's own serialized HTML, as returned by [`ElementRef::html`])
+/// found in `out`, returning whether it was found. Once that occurrence is
+/// rewritten it no longer matches `tag`, so calling this once per element -
+/// even when several elements serialize to identical tags - rewrites each
+/// occurrence exactly once instead of every occurrence of `value` across
+/// the whole document.
+///
+/// Leaves `out` unchanged and returns `false` if `tag` can't be found,
+/// which can happen if the HTML serializer re-quotes or reorders
+/// attributes relative to the original source; the caller is responsible
+/// for tracking how many assets this happens to, since it means the
+/// resulting snapshot is no longer fully self-contained.
+fn replace_attr_value(out: &mut String, tag: &str, value: &str, data_url: &str) -> bool {
+    match out.find(tag) {
+        Some(pos) => {
+            let replaced = tag.replacen(value, data_url, 1);
+            out.replace_range(pos..pos + tag.len(), &replaced);
+            true
+        }
+        None => {
+            log::warn!("Could not locate tag to inline asset: {tag}");
+            false
+        }
+    }
+}
+
+/// Replaces every `url(...)` reference inside a stylesheet with an inline
+/// `data:` URL, so fonts and background images embedded via CSS survive
+/// alongside the stylesheet itself.
+async fn embed_css_urls(
+    css: &str,
+    css_url: &Url,
+    stats: &Stats,
+    http: &HttpConfig,
+    objects: &ObjectStore,
+) -> Result<String, Error> {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+
+    while let Some(start) = rest.find("url(") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 4..];
+        let end = after
+            .find(')')
+            .ok_or_else(|| Error::InvalidHtml("Unterminated url() in stylesheet".to_string()))?;
+        let raw = after[..end].trim().trim_matches(|c| c == '"' || c == '\'');
+
+        if raw.starts_with("data:") {
+            out.push_str("url(");
+            out.push_str(raw);
+            out.push(')');
+        } else {
+            let asset_url = css_url.join(raw)?;
+            let (_, bytes) = objects.fetch(&asset_url, http).await?;
+            stats
+                .snapshot_bytes
+                .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            let data_url = to_data_url(guess_mime(raw), &bytes);
+            out.push_str("url(\"");
+            out.push_str(&data_url);
+            out.push_str("\")");
+        }
+
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+fn to_data_url(mime: &str, bytes: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+fn guess_mime(filename: &str) -> &'static str {
+    match filename.rsplit('.').next() {
+        Some("css") => "text/css",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("woff2") => "font/woff2",
+        Some("woff") => "font/woff",
+        Some("ttf") => "font/ttf",
+        _ => "application/octet-stream",
+    }
+}
+
 fn get_stylesheet(doc: &Html, base_url: &Url) -> Result<Option<(Url, String)>, Error> {
     if let Some(link) = doc.select(&STYLESHEET_SEL).next() {
         let href = link
@@ -163,6 +1081,7 @@ lazy_static::lazy_static! {
     static ref BODY_MAIN_LIST_SEL: Selector = Selector::parse("body > main > a").unwrap();
     static ref BANNER_IMG_SEL: Selector = Selector::parse("body > header > img").unwrap();
     static ref PROFILE_IMG_SEL: Selector = Selector::parse("body > main > img").unwrap();
+    static ref IMG_SEL: Selector = Selector::parse("img").unwrap();
     static ref BODY_MAIN_H1: Selector = Selector::parse("body > main > h1").unwrap();
 }
 
@@ -180,6 +1099,14 @@ pub enum Error {
     Csv(#[from] csv::Error),
     #[error("Invalid HTML")]
     InvalidHtml(String),
+    #[error("Headless browser error")]
+    Browser(#[from] chromiumoxide::error::CdpError),
+    #[error("Headless browser rendering error: {0}")]
+    Render(String),
+    #[error("Retries exhausted fetching {0}")]
+    RetriesExhausted(String),
+    #[error("JSON serialization error")]
+    Json(#[from] serde_json::Error),
 }
 
 #[derive(Parser)]
@@ -194,6 +1121,39 @@ struct Opts {
     /// Disable local copy
     #[clap(long)]
     disable_snapshot: bool,
+    /// Inline all assets into a single self-contained snapshot file
+    #[clap(long)]
+    embed: bool,
+    /// Number of user pages to download concurrently
+    #[clap(long, default_value = "4", value_parser = clap::value_parser!(usize).range(1..))]
+    concurrency: usize,
+    /// Render pages in a headless Chromium instance instead of plain HTTP
+    #[clap(long)]
+    render: bool,
+    /// CSS selector to wait for before reading rendered page content
+    #[clap(long)]
+    render_wait_selector: Option<String>,
+    /// Milliseconds to wait for the page to settle when rendering
+    #[clap(long, default_value = "2000")]
+    render_timeout_ms: u64,
+    /// User-Agent header sent with every request
+    #[clap(long)]
+    user_agent: Option<String>,
+    /// Proxy URL to route requests through
+    #[clap(long)]
+    proxy: Option<String>,
+    /// Request timeout in seconds
+    #[clap(long, default_value = "30")]
+    timeout: u64,
+    /// Maximum number of retries for a failed request
+    #[clap(long, default_value = "3")]
+    max_retries: u32,
+    /// Prior snapshot timestamp to diff this run's links against
+    #[clap(long)]
+    since: Option<String>,
+    /// Output format for the scraped links
+    #[clap(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
 }
 
 fn select_log_level_filter(verbosity: u8) -> LevelFilter {
@@ -215,3 +1175,205 @@ fn init_logging(verbosity: u8) -> Result<(), log::SetLoggerError> {
         simplelog::ColorChoice::Auto,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guess_mime_matches_on_extension() {
+        assert_eq!(guess_mime("style.css"), "text/css");
+        assert_eq!(guess_mime("banner.jpg"), "image/jpeg");
+        assert_eq!(guess_mime("banner.jpeg"), "image/jpeg");
+        assert_eq!(guess_mime("icons/font.woff2"), "font/woff2");
+        assert_eq!(guess_mime("no-extension"), "application/octet-stream");
+        assert_eq!(guess_mime("archive.tar.gz"), "application/octet-stream");
+    }
+
+    #[test]
+    fn to_data_url_base64_encodes_with_the_given_mime() {
+        let url = to_data_url("text/plain", b"hello");
+        assert_eq!(url, "data:text/plain;base64,aGVsbG8=");
+    }
+
+    #[tokio::test]
+    async fn embed_css_urls_passes_through_existing_data_urls_untouched() {
+        let stats = Stats::default();
+        let http = HttpConfig {
+            client: reqwest::Client::new(),
+            max_retries: 0,
+        };
+        let objects = ObjectStore::load(std::env::temp_dir().join("scrape-test-unused"));
+        let css_url = Url::parse("https://example.com/style.css").unwrap();
+        let css = "body { background: url(\"data:image/png;base64,AA==\"); }";
+
+        let out = embed_css_urls(css, &css_url, &stats, &http, &objects)
+            .await
+            .unwrap();
+
+        // The surrounding quotes aren't preserved, but the data: URL itself
+        // (the part that matters - no network fetch) passes through as-is.
+        assert_eq!(out, "body { background: url(data:image/png;base64,AA==); }");
+    }
+
+    #[test]
+    fn is_retryable_status_covers_server_errors_and_429() {
+        assert!(is_retryable_status(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR
+        ));
+        assert!(is_retryable_status(
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(is_retryable_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(reqwest::StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(reqwest::StatusCode::OK));
+    }
+
+    #[test]
+    fn retry_after_parses_the_seconds_header() {
+        let response: reqwest::Response = http::Response::builder()
+            .status(503)
+            .header(reqwest::header::RETRY_AFTER, "5")
+            .body(Vec::new())
+            .unwrap()
+            .into();
+
+        assert_eq!(retry_after(&response), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn retry_after_is_none_without_the_header() {
+        let response: reqwest::Response = http::Response::builder()
+            .status(503)
+            .body(Vec::new())
+            .unwrap()
+            .into();
+
+        assert_eq!(retry_after(&response), None);
+    }
+
+    fn sample_rows() -> Vec<(String, String, String, String)> {
+        vec![
+            (
+                "alice".to_string(),
+                "Alice".to_string(),
+                "t1".to_string(),
+                "https://a.example/1".to_string(),
+            ),
+            (
+                "alice".to_string(),
+                "Alice".to_string(),
+                "t2".to_string(),
+                "https://a.example/2".to_string(),
+            ),
+            (
+                "bob".to_string(),
+                "Bob".to_string(),
+                "t3".to_string(),
+                "https://b.example/1".to_string(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn to_link_map_groups_urls_by_screen_name() {
+        let map = to_link_map(&sample_rows());
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            map["alice"],
+            HashSet::from([
+                "https://a.example/1".to_string(),
+                "https://a.example/2".to_string()
+            ])
+        );
+        assert_eq!(
+            map["bob"],
+            HashSet::from(["https://b.example/1".to_string()])
+        );
+    }
+
+    #[test]
+    fn write_then_read_links_file_round_trips_through_to_link_map() {
+        let rows = sample_rows();
+        let dir = std::env::temp_dir().join(format!(
+            "scrape-test-links-{}-{}",
+            std::process::id(),
+            "round-trip"
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(LINKS_FILE_NAME);
+
+        write_links_file(&path, &rows).unwrap();
+        let read_back = read_links_file(&path).unwrap();
+
+        assert_eq!(read_back, to_link_map(&rows));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn to_link_map_reports_no_links_gained_or_lost_for_unchanged_rows() {
+        let rows = sample_rows();
+        let old = to_link_map(&rows);
+        let new = to_link_map(&rows);
+
+        for screen_name in old.keys() {
+            let gained = new[screen_name].difference(&old[screen_name]).count();
+            let lost = old[screen_name].difference(&new[screen_name]).count();
+            assert_eq!((gained, lost), (0, 0));
+        }
+    }
+
+    fn sample_user(screen_name: &str) -> User {
+        User {
+            screen_name: screen_name.to_string(),
+            display_name: screen_name.to_string(),
+            links: vec![Link {
+                title: "a link".to_string(),
+                url: "https://example.com".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn ndjson_writer_emits_one_json_object_per_line() {
+        let mut writer: Writer<Vec<u8>> = Writer::Ndjson(Vec::new());
+        writer.write_user(&sample_user("alice")).unwrap();
+        writer.write_user(&sample_user("bob")).unwrap();
+        writer.finish().unwrap();
+
+        let Writer::Ndjson(out) = writer else {
+            unreachable!()
+        };
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(value.get("screen_name").is_some());
+        }
+    }
+
+    #[test]
+    fn json_writer_frames_users_as_a_single_array() {
+        let mut writer = Writer::Json {
+            out: Vec::new(),
+            first: true,
+        };
+        writer.write_user(&sample_user("alice")).unwrap();
+        writer.write_user(&sample_user("bob")).unwrap();
+        writer.finish().unwrap();
+
+        let Writer::Json { out, .. } = writer else {
+            unreachable!()
+        };
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.starts_with('['));
+        assert!(text.trim_end().ends_with(']'));
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 2);
+    }
+}